@@ -0,0 +1,147 @@
+use crate::runtime::Inner;
+use crossbeam_queue::SegQueue;
+use futures_channel::oneshot;
+use std::{
+    cell::UnsafeCell,
+    future::Future,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context as TaskContext, Poll},
+    thread,
+};
+
+/// A futures-aware mutual-exclusion lock around an [`Inner`].
+///
+/// Unlike a [`std::sync::Mutex`], waiting for the lock from an async task does not block the
+/// executor thread: [`AsyncLock::lock_async`] parks the waiting task behind a oneshot channel
+/// instead, so other tasks on the same executor can keep making progress while one task waits
+/// its turn at the interpreter.
+#[derive(Debug)]
+pub(crate) struct AsyncLock {
+    inner: UnsafeCell<Inner>,
+    locked: AtomicBool,
+    waiters: SegQueue<oneshot::Sender<()>>,
+}
+
+unsafe impl Send for AsyncLock {}
+unsafe impl Sync for AsyncLock {}
+
+impl AsyncLock {
+    pub(crate) fn new(inner: Inner) -> Arc<Self> {
+        Arc::new(AsyncLock {
+            inner: UnsafeCell::new(inner),
+            locked: AtomicBool::new(false),
+            waiters: SegQueue::new(),
+        })
+    }
+
+    fn try_acquire(&self) -> bool {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    fn release(&self) {
+        // Hand the lock straight to the next waiter instead of clearing `locked`, so a waiting
+        // task can never be starved by a synchronous `lock()` racing in ahead of it.
+        while let Some(sender) = self.waiters.pop() {
+            if sender.send(()).is_ok() {
+                return;
+            }
+        }
+        self.locked.store(false, Ordering::Release);
+    }
+
+    /// Acquire the lock, blocking the current thread until it is free.
+    pub(crate) fn lock(self: &Arc<Self>) -> Guard {
+        loop {
+            if self.try_acquire() {
+                return Guard(self.clone());
+            }
+            thread::yield_now();
+        }
+    }
+
+    pub(crate) fn try_lock(self: &Arc<Self>) -> Option<Guard> {
+        if self.try_acquire() {
+            Some(Guard(self.clone()))
+        } else {
+            None
+        }
+    }
+
+    /// Acquire the lock without blocking the executor thread while waiting.
+    pub(crate) fn lock_async(self: &Arc<Self>) -> LockFuture {
+        LockFuture {
+            lock: self.clone(),
+            receiver: None,
+        }
+    }
+}
+
+/// Guard holding the lock. Releases it, waking the next waiter, on drop.
+#[derive(Debug)]
+pub(crate) struct Guard(Arc<AsyncLock>);
+
+impl Deref for Guard {
+    type Target = Inner;
+
+    fn deref(&self) -> &Inner {
+        unsafe { &*self.0.inner.get() }
+    }
+}
+
+impl DerefMut for Guard {
+    fn deref_mut(&mut self) -> &mut Inner {
+        unsafe { &mut *self.0.inner.get() }
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+/// Future returned by [`AsyncLock::lock_async`].
+pub(crate) struct LockFuture {
+    lock: Arc<AsyncLock>,
+    receiver: Option<oneshot::Receiver<()>>,
+}
+
+impl Future for LockFuture {
+    type Output = Guard;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Guard> {
+        if let Some(receiver) = self.receiver.as_mut() {
+            return match Pin::new(receiver).poll(cx) {
+                Poll::Ready(_) => Poll::Ready(Guard(self.lock.clone())),
+                Poll::Pending => Poll::Pending,
+            };
+        }
+        if self.lock.try_acquire() {
+            return Poll::Ready(Guard(self.lock.clone()));
+        }
+        let (sender, mut receiver) = oneshot::channel();
+        self.lock.waiters.push(sender);
+        // The holder's `release()` may have already run out the queue and cleared `locked`
+        // between our failed `try_acquire` above and the `push` just now, in which case no
+        // future `release()` call will ever see (and wake) the sender we just enqueued. Re-check
+        // here so that race can't leave us parked forever; if it fires, our sender is simply
+        // skipped over (and its failed send swallowed) by whichever `release()` pops it later.
+        if self.lock.try_acquire() {
+            return Poll::Ready(Guard(self.lock.clone()));
+        }
+        match Pin::new(&mut receiver).poll(cx) {
+            Poll::Ready(_) => Poll::Ready(Guard(self.lock.clone())),
+            Poll::Pending => {
+                self.receiver = Some(receiver);
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use crate::{error::get_exception, markers::Invariant, Error, Result, Runtime, Value};
+use rquickjs_sys as qjs;
+use std::{
+    ffi::CString,
+    marker::PhantomData,
+    mem,
+    os::raw::c_void,
+};
+
+/// Precompiled quickjs bytecode, produced by [`Context::compile`] and runnable with
+/// [`Context::run_bytecode`].
+///
+/// Opaque and only meaningful to the same quickjs version that produced it, but otherwise a
+/// plain byte buffer that can be written to and read back from disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bytecode(Vec<u8>);
+
+impl Bytecode {
+    /// The raw bytecode bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for Bytecode {
+    fn from(bytes: Vec<u8>) -> Self {
+        Bytecode(bytes)
+    }
+}
+
+impl From<Bytecode> for Vec<u8> {
+    fn from(bytecode: Bytecode) -> Self {
+        bytecode.0
+    }
+}
+
+/// A javascript context.
+///
+/// Contexts of the same runtime may share javascript objects like frames of the same origin in a
+/// browser. Clones share the same underlying quickjs context; dropping a `Context`, or every
+/// clone of it, does not free that quickjs context.
+#[derive(Clone, Debug)]
+pub struct Context {
+    rt: Runtime,
+    pub(crate) ctx: *mut qjs::JSContext,
+}
+
+/// Builder for creating a [`Context`]. It has no options: every context it builds has the full
+/// set of intrinsics.
+pub struct ContextBuilder {
+    rt: Runtime,
+}
+
+impl ContextBuilder {
+    pub fn new(rt: &Runtime) -> Self {
+        ContextBuilder { rt: rt.clone() }
+    }
+
+    /// Build the context with the full set of intrinsics enabled.
+    pub fn build(self) -> Result<Context> {
+        Context::full(&self.rt)
+    }
+}
+
+impl Context {
+    /// Create a context with all quickjs intrinsics (`Math`, `JSON`, typed arrays, etc.) enabled.
+    pub fn full(rt: &Runtime) -> Result<Self> {
+        let guard = rt.inner.lock();
+        let ctx = unsafe { qjs::JS_NewContext(guard.rt) };
+        mem::drop(guard);
+        if ctx.is_null() {
+            return Err(Error::Allocation);
+        }
+        Ok(Context { rt: rt.clone(), ctx })
+    }
+
+    /// Run `f` with a [`Ctx`](crate::Ctx) handle to this context, locking the runtime for the
+    /// duration of the closure.
+    pub fn with<'a, F, R>(&'a self, f: F) -> R
+    where
+        F: FnOnce(Ctx<'a>) -> R,
+    {
+        let guard = self.rt.inner.lock();
+        let result = f(unsafe { Ctx::new(self.ctx) });
+        mem::drop(guard);
+        result
+    }
+
+    /// Compile `source` to quickjs bytecode without evaluating it.
+    ///
+    /// The result can be persisted and later run with [`Context::run_bytecode`], skipping parse
+    /// cost on hot paths.
+    pub fn compile(&self, name: &str, source: &str) -> Result<Bytecode> {
+        let guard = self.rt.inner.lock();
+        let csource = CString::new(source)?;
+        let cname = CString::new(name)?;
+        let func = unsafe {
+            qjs::JS_Eval(
+                self.ctx,
+                csource.as_ptr(),
+                source.len() as qjs::size_t,
+                cname.as_ptr(),
+                qjs::JS_EVAL_FLAG_COMPILE_ONLY as i32,
+            )
+        };
+        if unsafe { qjs::JS_IsException(func) } != 0 {
+            let err = unsafe { get_exception(self.ctx) };
+            mem::drop(guard);
+            return Err(err);
+        }
+        let mut len: qjs::size_t = 0;
+        let buf = unsafe {
+            qjs::JS_WriteObject(self.ctx, &mut len, func, qjs::JS_WRITE_OBJ_BYTECODE as i32)
+        };
+        unsafe { qjs::JS_FreeValue(self.ctx, func) };
+        if buf.is_null() {
+            let err = unsafe { get_exception(self.ctx) };
+            mem::drop(guard);
+            return Err(err);
+        }
+        let bytes = unsafe { std::slice::from_raw_parts(buf, len as usize) }.to_vec();
+        unsafe { qjs::js_free(self.ctx, buf as *mut c_void) };
+        mem::drop(guard);
+        Ok(Bytecode(bytes))
+    }
+
+    /// Evaluate `source` and drain the runtime's job queue before returning, so that top-level
+    /// `await` and any promises resolved by the script have observably settled by the time the
+    /// value is read back into Rust.
+    pub fn eval_and_settle<S: Into<Vec<u8>>>(&self, source: S) -> Result<Value<'_>> {
+        let value = self.with(|ctx| ctx.eval(source))?;
+        self.rt.execute_pending_jobs()?;
+        Ok(value)
+    }
+
+    /// Evaluate `source` as an ES module and drain the runtime's job queue before returning.
+    ///
+    /// `import` statements in `source` (and transitively, in any module it imports) are resolved
+    /// through the loader registered with [`Runtime::set_module_loader`]. Draining the job queue
+    /// afterwards settles top-level `await` the same way [`eval_and_settle`](Self::eval_and_settle)
+    /// does for scripts.
+    ///
+    /// [`Runtime::set_module_loader`]: crate::Runtime::set_module_loader
+    pub fn eval_module<S: Into<Vec<u8>>>(&self, source: S) -> Result<Value<'_>> {
+        let value = self.with(|ctx| ctx.eval_module(source))?;
+        self.rt.execute_pending_jobs()?;
+        Ok(value)
+    }
+
+    /// Reconstruct and evaluate bytecode previously produced by [`Context::compile`].
+    pub fn run_bytecode(&self, bytecode: &Bytecode) -> Result<Value<'_>> {
+        let guard = self.rt.inner.lock();
+        let func = unsafe {
+            qjs::JS_ReadObject(
+                self.ctx,
+                bytecode.0.as_ptr(),
+                bytecode.0.len() as qjs::size_t,
+                qjs::JS_READ_OBJ_BYTECODE as i32,
+            )
+        };
+        if unsafe { qjs::JS_IsException(func) } != 0 {
+            let err = unsafe { get_exception(self.ctx) };
+            mem::drop(guard);
+            return Err(err);
+        }
+        let value = unsafe { qjs::JS_EvalFunction(self.ctx, func) };
+        if unsafe { qjs::JS_IsException(value) } != 0 {
+            let err = unsafe { get_exception(self.ctx) };
+            mem::drop(guard);
+            return Err(err);
+        }
+        mem::drop(guard);
+        Ok(unsafe { Value::from_js_value(self.ctx, value) })
+    }
+}
+
+/// A handle to a [`Context`], borrowed for the duration of a call to [`Context::with`].
+pub struct Ctx<'js> {
+    ctx: *mut qjs::JSContext,
+    marker: Invariant<'js>,
+}
+
+impl<'js> Ctx<'js> {
+    /// # Safety
+    /// `ctx` must be a valid context, locked for the lifetime `'js`.
+    pub(crate) unsafe fn new(ctx: *mut qjs::JSContext) -> Self {
+        Ctx {
+            ctx,
+            marker: PhantomData,
+        }
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut qjs::JSContext {
+        self.ctx
+    }
+
+    /// Evaluate `source` as a script and return the resulting value.
+    pub fn eval<S: Into<Vec<u8>>>(self, source: S) -> Result<Value<'js>> {
+        let source = CString::new(source)?;
+        let name = CString::new("eval_script")?;
+        let value = unsafe {
+            qjs::JS_Eval(
+                self.ctx,
+                source.as_ptr(),
+                source.as_bytes().len() as qjs::size_t,
+                name.as_ptr(),
+                qjs::JS_EVAL_TYPE_GLOBAL as i32,
+            )
+        };
+        if unsafe { qjs::JS_IsException(value) } != 0 {
+            return Err(unsafe { get_exception(self.ctx) });
+        }
+        Ok(unsafe { Value::from_js_value(self.ctx, value) })
+    }
+
+    /// Evaluate `source` as an ES module and return the module's resulting value.
+    ///
+    /// Unlike [`eval`](Self::eval), `import` statements are resolved through the loader
+    /// registered with [`Runtime::set_module_loader`](crate::Runtime::set_module_loader).
+    pub fn eval_module<S: Into<Vec<u8>>>(self, source: S) -> Result<Value<'js>> {
+        let source = CString::new(source)?;
+        let name = CString::new("eval_module")?;
+        let value = unsafe {
+            qjs::JS_Eval(
+                self.ctx,
+                source.as_ptr(),
+                source.as_bytes().len() as qjs::size_t,
+                name.as_ptr(),
+                qjs::JS_EVAL_TYPE_MODULE as i32,
+            )
+        };
+        if unsafe { qjs::JS_IsException(value) } != 0 {
+            return Err(unsafe { get_exception(self.ctx) });
+        }
+        Ok(unsafe { Value::from_js_value(self.ctx, value) })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn compile_and_run_bytecode() {
+        let rt = Runtime::new().unwrap();
+        let ctx = Context::full(&rt).unwrap();
+        let bytecode = ctx.compile("test", "1 + 1").unwrap();
+        let _value = ctx.run_bytecode(&bytecode).unwrap();
+    }
+}
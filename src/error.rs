@@ -0,0 +1,57 @@
+use crate::Error;
+use rquickjs_sys as qjs;
+use std::ffi::{CStr, CString};
+
+unsafe fn to_owned_string(ctx: *mut qjs::JSContext, value: qjs::JSValue) -> Option<String> {
+    let cstr = qjs::JS_ToCString(ctx, value);
+    if cstr.is_null() {
+        return None;
+    }
+    let owned = CStr::from_ptr(cstr).to_string_lossy().into_owned();
+    qjs::JS_FreeCString(ctx, cstr);
+    Some(owned)
+}
+
+unsafe fn get_string_property(ctx: *mut qjs::JSContext, obj: qjs::JSValue, name: &str) -> Option<String> {
+    let cname = CString::new(name).ok()?;
+    let value = qjs::JS_GetPropertyStr(ctx, obj, cname.as_ptr());
+    let string = if qjs::JS_IsUndefined(value) != 0 {
+        None
+    } else {
+        to_owned_string(ctx, value)
+    };
+    qjs::JS_FreeValue(ctx, value);
+    string
+}
+
+/// Drain the pending exception on `ctx` and turn it into an [`Error::Exception`].
+///
+/// If the thrown value is an `Error` object its `name`, `message` and `stack` properties are
+/// read individually; otherwise the raw value is converted to a string and used as the message.
+///
+/// # Safety
+/// `ctx` must be a valid, live context with an exception currently pending.
+pub(crate) unsafe fn get_exception(ctx: *mut qjs::JSContext) -> Error {
+    let value = qjs::JS_GetException(ctx);
+    let error = if qjs::JS_IsError(ctx, value) != 0 {
+        let name = get_string_property(ctx, value, "name");
+        let message = get_string_property(ctx, value, "message").unwrap_or_default();
+        let stack = get_string_property(ctx, value, "stack");
+        Error::Exception {
+            name,
+            message,
+            stack,
+        }
+    } else {
+        let message = to_owned_string(ctx, value).unwrap_or_else(|| {
+            String::from("quickjs produced an exception which could not be converted to a string")
+        });
+        Error::Exception {
+            name: None,
+            message,
+            stack: None,
+        }
+    };
+    qjs::JS_FreeValue(ctx, value);
+    error
+}
@@ -19,10 +19,15 @@
 use quick_error::quick_error;
 use std::{ffi::NulError, str};
 
+#[cfg(feature = "futures")]
+mod async_lock;
 mod context;
+mod error;
+mod module_loader;
 mod runtime;
-pub use context::{Context, ContextBuilder, Ctx};
-pub use runtime::Runtime;
+pub use context::{Bytecode, Context, ContextBuilder, Ctx};
+pub use module_loader::ModuleLoader;
+pub use runtime::{InterruptHandler, MemoryUsage, Runtime};
 mod markers;
 mod value;
 use std::result::Result as StdResult;
@@ -52,8 +57,12 @@ quick_error! {
         Unknown{
             display("quickjs library created a unknown error")
         }
-        Exception(e: StdString){
-            display("exception generated by quickjs: {}",e)
+        /// An uncaught exception thrown by running javascript.
+        ///
+        /// `name` and `stack` are populated when the thrown value is an `Error` object; for a
+        /// thrown primitive (`throw "oops"`) only `message` is available.
+        Exception{name: Option<StdString>, message: StdString, stack: Option<StdString>}{
+            display("{}: {}", name.as_deref().unwrap_or("Error"), message)
         }
         FromJsConversion{from: &'static str, to: &'static str, message: Option<StdString>} {
             display("error converting from js from type '{}', to '{}': {}",from,to,message.as_ref().unwrap_or(&StdString::new()))
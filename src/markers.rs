@@ -0,0 +1,6 @@
+use std::marker::PhantomData;
+
+/// Marker used to tie the lifetime of a value to the [`Ctx`](crate::Ctx) it was created from,
+/// without being either co- or contra-variant in `'js`, so a value from one context can never be
+/// smuggled into a shorter or longer lived one.
+pub(crate) type Invariant<'js> = PhantomData<*mut &'js ()>;
@@ -0,0 +1,18 @@
+use crate::Result;
+
+/// Trait for resolving and loading ES module source.
+///
+/// Implement this to back `import` statements with the filesystem, an in-memory map, a bundler,
+/// or any other source of module text, then register it with [`Runtime::set_module_loader`].
+///
+/// [`Runtime::set_module_loader`]: crate::Runtime::set_module_loader
+pub trait ModuleLoader: Send + Sync {
+    /// Turn a possibly-relative module specifier into a canonical module name.
+    ///
+    /// `base` is the name of the module doing the importing, `name` is the specifier as written
+    /// in the `import` statement. Return an error if `name` cannot be resolved against `base`.
+    fn normalize(&self, base: &str, name: &str) -> Result<String>;
+
+    /// Return the source text of the module previously resolved by [`normalize`](Self::normalize).
+    fn load(&self, name: &str) -> Result<Vec<u8>>;
+}
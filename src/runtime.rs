@@ -1,13 +1,27 @@
 use crate::Error;
 use rquickjs_sys as qjs;
-#[cfg(feature = "parallel")]
+#[cfg(all(feature = "parallel", not(feature = "futures")))]
 use std::sync::{Arc, Mutex, MutexGuard};
-#[cfg(not(feature = "parallel"))]
+#[cfg(not(any(feature = "parallel", feature = "futures")))]
 use std::{
     cell::{RefCell, RefMut},
     rc::Rc,
 };
-use std::{ffi::CString, mem, ptr};
+use std::{
+    ffi::{CStr, CString},
+    mem,
+    os::raw::{c_char, c_int, c_void},
+    ptr,
+};
+
+#[cfg(feature = "futures")]
+use crate::async_lock::{AsyncLock, Guard as AsyncGuard, LockFuture};
+use crate::{error::get_exception, ModuleLoader};
+
+/// Signature of a closure which can be registered with [`Runtime::set_interrupt_handler`].
+///
+/// Returning `true` interrupts the running script.
+pub type InterruptHandler = Box<dyn FnMut() -> bool + Send + 'static>;
 
 #[derive(Debug)]
 pub(crate) struct Inner {
@@ -15,36 +29,95 @@ pub(crate) struct Inner {
     pub(crate) rt: *mut qjs::JSRuntime,
     // Keep info alive for the entire duration of the lifetime of rt
     info: Option<CString>,
+    // Keep the handler alive for as long as quickjs might call it.
+    interrupt_handler: Option<Box<InterruptHandler>>,
+    // Keep the loader alive for the entire duration of the lifetime of rt
+    module_loader: Option<Box<Box<dyn ModuleLoader>>>,
 }
 
-#[cfg(not(feature = "parallel"))]
+#[cfg(not(any(feature = "parallel", feature = "futures")))]
 #[derive(Debug, Clone)]
 pub(crate) struct InnerRef(Rc<RefCell<Inner>>);
 
-#[cfg(feature = "parallel")]
+#[cfg(all(feature = "parallel", not(feature = "futures")))]
 #[derive(Debug, Clone)]
 pub(crate) struct InnerRef(Arc<Mutex<Inner>>);
 
+#[cfg(feature = "futures")]
+#[derive(Debug, Clone)]
+pub(crate) struct InnerRef(Arc<AsyncLock>);
+
 impl InnerRef {
-    #[cfg(not(feature = "parallel"))]
+    #[cfg(not(any(feature = "parallel", feature = "futures")))]
     pub fn lock(&self) -> RefMut<Inner> {
         self.0.borrow_mut()
     }
 
-    #[cfg(not(feature = "parallel"))]
+    #[cfg(not(any(feature = "parallel", feature = "futures")))]
     pub fn try_lock(&self) -> Option<RefMut<Inner>> {
         Some(self.0.borrow_mut())
     }
 
-    #[cfg(feature = "parallel")]
+    #[cfg(all(feature = "parallel", not(feature = "futures")))]
     pub fn lock(&self) -> MutexGuard<Inner> {
         self.0.lock().unwrap()
     }
 
-    #[cfg(feature = "parallel")]
+    #[cfg(all(feature = "parallel", not(feature = "futures")))]
     pub fn try_lock(&self) -> Option<RefMut<Inner>> {
         self.0.lock().ok()
     }
+
+    #[cfg(feature = "futures")]
+    pub fn lock(&self) -> AsyncGuard {
+        AsyncLock::lock(&self.0)
+    }
+
+    #[cfg(feature = "futures")]
+    pub fn try_lock(&self) -> Option<AsyncGuard> {
+        AsyncLock::try_lock(&self.0)
+    }
+
+    /// Acquire the lock without blocking the executor thread while waiting, for use inside an
+    /// async task.
+    #[cfg(feature = "futures")]
+    pub fn lock_async(&self) -> LockFuture {
+        AsyncLock::lock_async(&self.0)
+    }
+}
+
+/// A snapshot of quickjs's memory consumption, as returned by [`Runtime::memory_usage`].
+///
+/// Mirrors the fields of the underlying `JSMemoryUsage` C struct; see the quickjs documentation
+/// for the precise meaning of each counter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MemoryUsage {
+    pub malloc_count: i64,
+    pub malloc_size: i64,
+    pub malloc_limit: i64,
+    pub memory_used_count: i64,
+    pub memory_used_size: i64,
+    pub atom_count: i64,
+    pub atom_size: i64,
+    pub str_count: i64,
+    pub str_size: i64,
+    pub obj_count: i64,
+    pub obj_size: i64,
+    pub prop_count: i64,
+    pub prop_size: i64,
+    pub shape_count: i64,
+    pub shape_size: i64,
+    pub js_func_count: i64,
+    pub js_func_size: i64,
+    pub js_func_code_size: i64,
+    pub js_func_pc2line_count: i64,
+    pub js_func_pc2line_size: i64,
+    pub c_func_count: i64,
+    pub array_count: i64,
+    pub fast_array_count: i64,
+    pub fast_array_elements: i64,
+    pub binary_object_count: i64,
+    pub binary_object_size: i64,
 }
 
 /// Entry point of the library.
@@ -59,17 +132,31 @@ impl Runtime {
         if rt == ptr::null_mut() {
             return Err(Error::Allocation);
         }
-        #[cfg(not(feature = "parallel"))]
+        let inner = Inner {
+            rt,
+            info: None,
+            interrupt_handler: None,
+            module_loader: None,
+        };
+
+        #[cfg(not(any(feature = "parallel", feature = "futures")))]
+        {
+            Ok(Runtime {
+                inner: InnerRef(Rc::new(RefCell::new(inner))),
+            })
+        }
+
+        #[cfg(all(feature = "parallel", not(feature = "futures")))]
         {
             Ok(Runtime {
-                inner: InnerRef(Rc::new(RefCell::new(Inner { rt, info: None }))),
+                inner: InnerRef(Arc::new(Mutex::new(inner))),
             })
         }
 
-        #[cfg(feature = "parallel")]
+        #[cfg(feature = "futures")]
         {
             Ok(Runtime {
-                inner: InnerRef(Arc::new(Mutex::new(Inner { rt, info: None }))),
+                inner: InnerRef(AsyncLock::new(inner)),
             })
         }
     }
@@ -101,6 +188,193 @@ impl Runtime {
         unsafe { qjs::JS_RunGC(guard.rt) }
         mem::drop(guard);
     }
+
+    /// Snapshot quickjs's current memory consumption.
+    ///
+    /// Useful for tuning [`Runtime::set_memory_limit`]/[`Runtime::set_gc_threshold`] and for
+    /// memory dashboards and leak tests.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let guard = self.inner.lock();
+        let mut usage = mem::MaybeUninit::<qjs::JSMemoryUsage>::uninit();
+        unsafe { qjs::JS_ComputeMemoryUsage(guard.rt, usage.as_mut_ptr()) };
+        let usage = unsafe { usage.assume_init() };
+        mem::drop(guard);
+        MemoryUsage {
+            malloc_count: usage.malloc_count,
+            malloc_size: usage.malloc_size,
+            malloc_limit: usage.malloc_limit,
+            memory_used_count: usage.memory_used_count,
+            memory_used_size: usage.memory_used_size,
+            atom_count: usage.atom_count,
+            atom_size: usage.atom_size,
+            str_count: usage.str_count,
+            str_size: usage.str_size,
+            obj_count: usage.obj_count,
+            obj_size: usage.obj_size,
+            prop_count: usage.prop_count,
+            prop_size: usage.prop_size,
+            shape_count: usage.shape_count,
+            shape_size: usage.shape_size,
+            js_func_count: usage.js_func_count,
+            js_func_size: usage.js_func_size,
+            js_func_code_size: usage.js_func_code_size,
+            js_func_pc2line_count: usage.js_func_pc2line_count,
+            js_func_pc2line_size: usage.js_func_pc2line_size,
+            c_func_count: usage.c_func_count,
+            array_count: usage.array_count,
+            fast_array_count: usage.fast_array_count,
+            fast_array_elements: usage.fast_array_elements,
+            binary_object_count: usage.binary_object_count,
+            binary_object_size: usage.binary_object_size,
+        }
+    }
+
+    /// Drive the queue of pending Promise reactions and async/await continuations.
+    ///
+    /// Quickjs enqueues these as jobs rather than running them inline, so scripts using
+    /// `Promise.then` or `async`/`await` make no progress until the host pumps this queue.
+    /// Runs jobs until the queue is empty, returning `Ok(true)` if at least one job ran.
+    pub fn execute_pending_jobs(&self) -> Result<bool, Error> {
+        let guard = self.inner.lock();
+        let mut ran_any = false;
+        loop {
+            let mut ctx = ptr::null_mut();
+            let result = unsafe { qjs::JS_ExecutePendingJob(guard.rt, &mut ctx) };
+            if result == 0 {
+                break;
+            }
+            if result < 0 {
+                return Err(unsafe { get_exception(ctx) });
+            }
+            ran_any = true;
+        }
+        Ok(ran_any)
+    }
+
+    /// Check whether there are jobs waiting to be run by [`Runtime::execute_pending_jobs`].
+    pub fn is_job_pending(&self) -> bool {
+        let guard = self.inner.lock();
+        unsafe { qjs::JS_IsJobPending(guard.rt) != 0 }
+    }
+
+    /// Register a handler which quickjs periodically calls while running a script.
+    ///
+    /// Returning `true` from the handler aborts the currently running script, which surfaces to
+    /// the caller as [`Error::Exception`]. Useful for enforcing a deadline or cancellation flag
+    /// on long running or malicious scripts.
+    pub fn set_interrupt_handler(&self, handler: InterruptHandler) {
+        let mut guard = self.inner.lock();
+        let boxed = Box::new(handler);
+        let opaque = boxed.as_ref() as *const InterruptHandler as *mut c_void;
+        unsafe { qjs::JS_SetInterruptHandler(guard.rt, Some(interrupt_trampoline), opaque) }
+        // Only drop the previous handler after the new one is registered so the pointer quickjs
+        // holds is always valid.
+        let previous = guard.interrupt_handler.replace(boxed);
+        mem::drop(previous);
+    }
+
+    /// Remove a previously set interrupt handler, if any.
+    pub fn remove_interrupt_handler(&self) {
+        let mut guard = self.inner.lock();
+        unsafe { qjs::JS_SetInterruptHandler(guard.rt, None, ptr::null_mut()) }
+        guard.interrupt_handler.take();
+    }
+
+    /// Register a loader used to resolve and load the source of ES modules imported by scripts
+    /// run on this runtime.
+    pub fn set_module_loader(&self, loader: Box<dyn ModuleLoader>) {
+        let mut guard = self.inner.lock();
+        // `loader` is a fat pointer, so box it once more to get a thin pointer quickjs can carry
+        // around as an opaque `void*`.
+        let boxed = Box::new(loader);
+        let opaque = boxed.as_ref() as *const Box<dyn ModuleLoader> as *mut c_void;
+        unsafe {
+            qjs::JS_SetModuleLoaderFunc(
+                guard.rt,
+                Some(module_normalize_trampoline),
+                Some(module_loader_trampoline),
+                opaque,
+            )
+        }
+        // Only drop the previous loader after the new one is registered so the pointer quickjs
+        // holds is always valid.
+        let previous = guard.module_loader.replace(boxed);
+        mem::drop(previous);
+    }
+}
+
+unsafe extern "C" fn module_normalize_trampoline(
+    ctx: *mut qjs::JSContext,
+    base: *const c_char,
+    name: *const c_char,
+    opaque: *mut c_void,
+) -> *mut c_char {
+    let loader = &*(opaque as *const Box<dyn ModuleLoader>);
+    let base = CStr::from_ptr(base).to_string_lossy();
+    let name = CStr::from_ptr(name).to_string_lossy();
+    match loader
+        .normalize(&base, &name)
+        .and_then(|name| CString::new(name).map_err(Error::from))
+    {
+        Ok(normalized) => {
+            let bytes = normalized.as_bytes_with_nul();
+            let mem = qjs::js_malloc(ctx, bytes.len() as qjs::size_t) as *mut c_char;
+            if !mem.is_null() {
+                ptr::copy_nonoverlapping(bytes.as_ptr() as *const c_char, mem, bytes.len());
+            }
+            mem
+        }
+        Err(e) => {
+            let message = CString::new(e.to_string()).unwrap_or_else(|_| CString::new("module resolution failed").unwrap());
+            qjs::JS_ThrowTypeError(ctx, message.as_ptr());
+            ptr::null_mut()
+        }
+    }
+}
+
+unsafe extern "C" fn module_loader_trampoline(
+    ctx: *mut qjs::JSContext,
+    name: *const c_char,
+    opaque: *mut c_void,
+) -> *mut qjs::JSModuleDef {
+    let loader = &*(opaque as *const Box<dyn ModuleLoader>);
+    let name_str = CStr::from_ptr(name).to_string_lossy();
+    let source = match loader.load(&name_str) {
+        Ok(source) => source,
+        Err(e) => {
+            let message = CString::new(e.to_string()).unwrap_or_else(|_| CString::new("module load failed").unwrap());
+            qjs::JS_ThrowTypeError(ctx, message.as_ptr());
+            return ptr::null_mut();
+        }
+    };
+    let source = match CString::new(source) {
+        Ok(s) => s,
+        Err(_) => {
+            let message = CString::new("module source contained internal null bytes").unwrap();
+            qjs::JS_ThrowTypeError(ctx, message.as_ptr());
+            return ptr::null_mut();
+        }
+    };
+    let value = qjs::JS_Eval(
+        ctx,
+        source.as_ptr(),
+        source.as_bytes().len() as qjs::size_t,
+        name,
+        (qjs::JS_EVAL_TYPE_MODULE | qjs::JS_EVAL_FLAG_COMPILE_ONLY) as c_int,
+    );
+    if qjs::JS_IsException(value) != 0 {
+        return ptr::null_mut();
+    }
+    // The module is already referenced by quickjs's internal module table, so the `JSValue`
+    // handle `JS_Eval` returned here must be freed to avoid leaking that reference.
+    let module = qjs::JS_VALUE_GET_PTR(value) as *mut qjs::JSModuleDef;
+    qjs::JS_FreeValue(ctx, value);
+    module
+}
+
+unsafe extern "C" fn interrupt_trampoline(_rt: *mut qjs::JSRuntime, opaque: *mut c_void) -> c_int {
+    let handler = &mut *(opaque as *mut InterruptHandler);
+    handler() as c_int
 }
 
 impl Drop for Inner {
@@ -111,15 +385,18 @@ impl Drop for Inner {
 
 // Since all functions which use runtime are behind a mutex
 // sending the runtime to other threads should be fine.
-#[cfg(feature = "parallel")]
+#[cfg(all(feature = "parallel", not(feature = "futures")))]
 unsafe impl Send for Runtime {}
 
 // Since a global lock needs to be locked for safe use
 // using runtime in a sync way should be safe as
 // simultanious accesses is syncronized behind a lock.
-#[cfg(feature = "parallel")]
+#[cfg(all(feature = "parallel", not(feature = "futures")))]
 unsafe impl Sync for Runtime {}
 
+// Under `futures` the lock itself is Send + Sync (see `async_lock::AsyncLock`), so Runtime
+// inherits both automatically and needs no manual impl.
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -131,4 +408,64 @@ mod test {
         rt.set_gc_threshold(0xFF);
         rt.run_gc();
     }
+
+    #[test]
+    fn interrupt_handler() {
+        let rt = Runtime::new().unwrap();
+        rt.set_interrupt_handler(Box::new(|| true));
+        rt.remove_interrupt_handler();
+    }
+
+    struct TestLoader;
+
+    impl ModuleLoader for TestLoader {
+        fn normalize(&self, _base: &str, name: &str) -> crate::Result<String> {
+            Ok(name.to_string())
+        }
+
+        fn load(&self, _name: &str) -> crate::Result<Vec<u8>> {
+            Ok(b"export const value = 1;".to_vec())
+        }
+    }
+
+    #[test]
+    fn module_loader() {
+        let rt = Runtime::new().unwrap();
+        rt.set_module_loader(Box::new(TestLoader));
+    }
+
+    #[test]
+    fn module_loader_resolves_import() {
+        let rt = Runtime::new().unwrap();
+        rt.set_module_loader(Box::new(TestLoader));
+        let ctx = crate::Context::full(&rt).unwrap();
+        // Evaluating this as a module routes the `import` through `TestLoader::normalize` and
+        // `TestLoader::load`, proving the loader registered above is actually reachable.
+        ctx.eval_module("import { value } from 'dep'; globalThis.imported = value;")
+            .unwrap();
+    }
+
+    #[test]
+    fn pending_jobs() {
+        let rt = Runtime::new().unwrap();
+        assert!(!rt.is_job_pending());
+        assert_eq!(rt.execute_pending_jobs().unwrap(), false);
+    }
+
+    #[test]
+    fn memory_usage() {
+        let rt = Runtime::new().unwrap();
+        let usage = rt.memory_usage();
+        assert!(usage.memory_used_size >= 0);
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn lock_async() {
+        futures_executor::block_on(async {
+            let rt = Runtime::new().unwrap();
+            let guard = rt.inner.lock_async().await;
+            mem::drop(guard);
+        });
+    }
 }
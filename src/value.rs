@@ -0,0 +1,34 @@
+use crate::markers::Invariant;
+use rquickjs_sys as qjs;
+use std::marker::PhantomData;
+
+/// A Javascript value.
+///
+/// Tied to the lifetime of the [`Ctx`](crate::Ctx) it was produced from, so it cannot outlive the
+/// context it belongs to.
+#[derive(Debug)]
+pub struct Value<'js> {
+    pub(crate) ctx: *mut qjs::JSContext,
+    pub(crate) value: qjs::JSValue,
+    marker: Invariant<'js>,
+}
+
+impl<'js> Value<'js> {
+    /// Wrap a raw `JSValue` returned from quickjs.
+    ///
+    /// # Safety
+    /// `value` must be a valid value owned by `ctx`.
+    pub(crate) unsafe fn from_js_value(ctx: *mut qjs::JSContext, value: qjs::JSValue) -> Self {
+        Value {
+            ctx,
+            value,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'js> Drop for Value<'js> {
+    fn drop(&mut self) {
+        unsafe { qjs::JS_FreeValue(self.ctx, self.value) }
+    }
+}